@@ -4,27 +4,40 @@
 #![no_std]
 #![no_main]
 
+use core::sync::atomic::{AtomicBool, AtomicU16, AtomicU8, Ordering};
+
 use assign_resources::assign_resources;
-use defmt::{info, unwrap};
+use defmt::{info, unwrap, warn};
+use embassy_futures::select::{select, select4, Either, Either4};
 use embassy_nrf::peripherals;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
-use embassy_time::Timer;
+use embassy_time::{Duration, Timer};
 use futures::future;
 use git_version::git_version;
 use heapless::String;
+use static_cell::StaticCell;
 
 use embassy_nrf::{
     gpio::{self, Input},
-    interrupt, Peripherals,
+    gpiote::{InputChannel, InputChannelPolarity, OutputChannel, OutputChannelPolarity},
+    interrupt,
+    ppi::Ppi,
+    timer::{Frequency, Timer as HwTimer},
+    Peripherals,
 };
 use nrf_softdevice::{
-    ble::{self, advertisement_builder as advb, peripheral as blep},
+    ble::{self, advertisement_builder as advb, peripheral as blep, IdentityKey},
     raw as nrf_defines, Softdevice,
 };
 
 use defmt_rtt as _;
 use panic_probe as _;
 
+mod persist;
+mod security;
+
+use security::Bonder;
+
 // There are only 2 'resources' - still, good for consistency across projects :)
 assign_resources! {
     button: ButtonResources {
@@ -32,40 +45,127 @@ assign_resources! {
     },
     triac: TriacResources {
         pin: P0_20,
+        zero_cross: P0_21,
+        timer: TIMER1,
+        zero_cross_gpiote: GPIOTE_CH0,
+        gate_latch_gpiote: GPIOTE_CH1,
+        gate_release_gpiote: GPIOTE_CH2,
+        restart_ppi: PPI_CH0,
+        low_ppi: PPI_CH1,
+        high_ppi: PPI_CH2,
     }
 }
 
+/// AC mains half-cycle duration at 50 Hz, i.e. the time between zero crossings.
+const MAINS_HALF_PERIOD: Duration = Duration::from_micros(10_000);
+
+/// How long the gate is pulsed low to latch the triac.
+const GATE_PULSE: Duration = Duration::from_micros(150);
+
+/// Brightness the triac task starts at before any `brightness` write arrives.
+/// Brightness isn't persisted (yet), so this also has to be pushed into the
+/// `brightness` characteristic at boot to keep it in sync.
+const DEFAULT_BRIGHTNESS: u8 = 100;
+
+/// Zero-cross edges closer together than this are assumed to be opto-isolator
+/// noise rather than an actual mains crossing, and are debounced in software
+/// by briefly disabling `restart_ppi` - see `triac_controller_run`.
+const ZERO_CROSS_DEBOUNCE: Duration = Duration::from_millis(1);
+
 /// Actions used for triac control channel
 #[derive(defmt::Format)]
 enum TriacAction {
     On,
     Off,
     Toggle,
+    Brightness(u8),
 }
 
 /// Channel used to send messages to triac
 type TriacSignal = Signal<CriticalSectionRawMutex, TriacAction>;
 
-/// Gatt configuration. A single 'custom' control service containing everything needed.
+/// Channel used to hand the currently active BLE connection to the triac
+/// task, so it knows where (and whether) to send `triac_control` notifications.
+type ConnectionSignal = Signal<CriticalSectionRawMutex, ble::Connection>;
+
+/// Channel used by the triac task to report on/off changes to the flash
+/// persistence task.
+pub(crate) type StateSignal = Signal<CriticalSectionRawMutex, bool>;
+
+/// Channel used to arm, re-arm or cancel the auto-off countdown. A value of
+/// 0 cancels any pending countdown.
+type CountdownSignal = Signal<CriticalSectionRawMutex, u16>;
+
+/// Authoritative on/off state of the lamp. Owned by the triac task, which is
+/// the only writer; other tasks may read it without needing a connection or
+/// a round-trip through `TriacSignal`.
+static LAMP_STATE: AtomicBool = AtomicBool::new(false);
+
+/// Authoritative brightness and countdown state, mirroring `LAMP_STATE`.
+/// These exist so a write rejected by `bluetooth_task_run` (because the
+/// connection isn't bonded) can be reverted in the GATT attribute table: the
+/// softdevice applies an incoming write to its table before our event
+/// handler ever sees it, so merely refusing to act on the event isn't
+/// enough to stop a later legitimate read from returning the rejected value.
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(DEFAULT_BRIGHTNESS);
+static COUNTDOWN_SECS: AtomicU16 = AtomicU16::new(0);
+
+/// Our 'custom' control service, holding everything needed to drive the lamp.
 #[nrf_softdevice::gatt_service(uuid = "c831c2f2-817f-11ee-b962-0242ac120002")]
 pub struct ControlService {
     /// Control point that controls the lamp
-    #[characteristic(uuid = "c831c2f2-817f-11ee-b962-0242ac130002", read, write)]
+    #[characteristic(uuid = "c831c2f2-817f-11ee-b962-0242ac130002", read, write, notify)]
     triac_control: bool,
 
-    /// Version string of this firmware for debugging
-    #[characteristic(uuid = "c831c2f2-817f-11ee-b962-0242ac140002", read)]
-    version: String<32>,
+    /// Dimming level in percent, 0-100. 0 keeps the lamp off no matter what
+    /// `triac_control` says, 100 is fully on.
+    #[characteristic(uuid = "c831c2f2-817f-11ee-b962-0242ac150002", read, write)]
+    brightness: u8,
+
+    /// Auto-off countdown in seconds. Writing a non-zero value arms a timer
+    /// that turns the lamp off once it elapses; writing 0 cancels it. Each
+    /// write replaces any countdown already running rather than stacking.
+    #[characteristic(uuid = "c831c2f2-817f-11ee-b962-0242ac160002", read, write)]
+    countdown_secs: u16,
+}
+
+/// Standard Device Information Service (0x180A). Lets generic BLE tools and
+/// home-automation hubs identify the switch without parsing our
+/// vendor-specific UUID; `ControlService` stays focused on control.
+#[nrf_softdevice::gatt_service(uuid = "180a")]
+pub struct DeviceInformationService {
+    #[characteristic(uuid = "2a29", read)]
+    manufacturer_name: String<32>,
+
+    #[characteristic(uuid = "2a24", read)]
+    model_number: String<32>,
+
+    #[characteristic(uuid = "2a27", read)]
+    hardware_revision: String<32>,
+
+    /// Firmware git revision, for debugging devices in the field.
+    #[characteristic(uuid = "2a28", read)]
+    software_revision: String<32>,
 }
 
 /// A gatt server that holds all of our characteristics
 #[nrf_softdevice::gatt_server]
 pub struct GattServer {
     control: ControlService,
+    info: DeviceInformationService,
 }
 
-/// Runs advertisement cycle. Returns connection that we can feed to the gatt_server.
-async fn advertise(softdevice: &Softdevice) -> Result<ble::Connection, blep::AdvertiseError> {
+/// Runs one advertisement cycle. Returns connection that we can feed to the
+/// gatt_server.
+///
+/// When `peer` is `Some`, advertises directed at that already-bonded peer for
+/// a fast, secure reconnect. Otherwise falls back to the regular open
+/// advertising, used when there's no bond yet or the pairing button combo
+/// was held at boot.
+async fn advertise(
+    softdevice: &Softdevice,
+    peer: Option<IdentityKey>,
+) -> Result<ble::Connection, blep::AdvertiseError> {
     static ADV_DATA: advb::LegacyAdvertisementPayload = advb::LegacyAdvertisementBuilder::new()
         .flags(&[advb::Flag::GeneralDiscovery, advb::Flag::LE_Only])
         .full_name("Nordic Switch")
@@ -74,46 +174,91 @@ async fn advertise(softdevice: &Softdevice) -> Result<ble::Connection, blep::Adv
     static SCAN_DATA: advb::LegacyAdvertisementPayload =
         advb::LegacyAdvertisementBuilder::new().build();
 
-    let packet = blep::ConnectableAdvertisement::ScannableUndirected {
-        adv_data: &ADV_DATA,
-        scan_data: &SCAN_DATA,
-    };
-
     let config = blep::Config {
         interval: 1600, // 1s
         ..Default::default()
     };
 
-    blep::advertise_connectable(softdevice, packet, &config).await
+    if let Some(peer) = peer {
+        let packet = blep::ConnectableAdvertisement::NonscannableDirected { peer: peer.addr };
+        blep::advertise_connectable(softdevice, packet, &config).await
+    } else {
+        let packet = blep::ConnectableAdvertisement::ScannableUndirected {
+            adv_data: &ADV_DATA,
+            scan_data: &SCAN_DATA,
+        };
+        blep::advertise_connectable(softdevice, packet, &config).await
+    }
 }
 
 /// Task that handles all the bluetooth stuff
 #[embassy_executor::task]
-async fn bluetooth_task_run(softdevice: &'static mut Softdevice, triac: &'static TriacSignal) {
-    let gatt = unwrap!(GattServer::new(softdevice));
-
-    // Store the git version in the control service to help debug issues in the field
-    unwrap!(gatt
-        .control
-        .version_set(&unwrap!(git_version!().try_into())));
-
-    // That's how we handle all events coming from the gatt_server
-    let event_handler = |e: GattServerEvent| match e {
-        GattServerEvent::Control(event) => match event {
-            ControlServiceEvent::TriacControlWrite(on) => triac.signal(if on {
-                TriacAction::On
-            } else {
-                TriacAction::Off
-            }),
-        },
-    };
-
+async fn bluetooth_task_run(
+    softdevice: &'static mut Softdevice,
+    gatt: &'static GattServer,
+    triac: &'static TriacSignal,
+    connection_signal: &'static ConnectionSignal,
+    bonder: &'static Bonder,
+    force_open_advertising: bool,
+    countdown: &'static CountdownSignal,
+) {
     future::join(
         // Run connection cycle
         async {
             loop {
-                if let Ok(connection) = advertise(softdevice).await {
-                    ble::gatt_server::run(&connection, &gatt, event_handler).await;
+                let peer = (!force_open_advertising).then(|| bonder.peer()).flatten();
+
+                if let Ok(connection) = advertise(softdevice, peer).await {
+                    // Hand the connection to the triac task so it can notify
+                    // `triac_control` changes back to the client.
+                    connection_signal.signal(connection.clone());
+
+                    // That's how we handle all events coming from the gatt_server
+                    let event_handler = |e: GattServerEvent| match e {
+                        GattServerEvent::Control(event) => {
+                            if !security::is_secure(&connection, bonder) {
+                                warn!("rejecting control write from an unencrypted/unbonded connection");
+
+                                // The write already landed in the attribute
+                                // table by the time we get this event -
+                                // stomp it back to the authoritative value so
+                                // a later plain read doesn't leak it.
+                                match event {
+                                    ControlServiceEvent::TriacControlWrite(_) => {
+                                        let _ = gatt
+                                            .control
+                                            .triac_control_set(&LAMP_STATE.load(Ordering::Relaxed));
+                                    }
+                                    ControlServiceEvent::BrightnessWrite(_) => {
+                                        let _ = gatt
+                                            .control
+                                            .brightness_set(&BRIGHTNESS.load(Ordering::Relaxed));
+                                    }
+                                    ControlServiceEvent::CountdownSecsWrite(_) => {
+                                        let _ = gatt
+                                            .control
+                                            .countdown_secs_set(&COUNTDOWN_SECS.load(Ordering::Relaxed));
+                                    }
+                                }
+
+                                return;
+                            }
+
+                            match event {
+                                ControlServiceEvent::TriacControlWrite(on) => {
+                                    triac.signal(if on { TriacAction::On } else { TriacAction::Off })
+                                }
+                                ControlServiceEvent::BrightnessWrite(level) => {
+                                    triac.signal(TriacAction::Brightness(level))
+                                }
+                                ControlServiceEvent::CountdownSecsWrite(secs) => {
+                                    countdown.signal(secs)
+                                }
+                            }
+                        }
+                    };
+
+                    ble::gatt_server::run(&connection, gatt, event_handler).await;
                 }
             }
         },
@@ -123,19 +268,203 @@ async fn bluetooth_task_run(softdevice: &'static mut Softdevice, triac: &'static
     .await;
 }
 
-/// Task that listens for triac signals and reacts appropriately
+/// Task that does leading-edge phase-angle dimming and reacts to triac
+/// actions (on/off/brightness, notifying, arming the countdown).
+///
+/// The gate pulse itself - the part that actually has to land within a few
+/// hundred microseconds of the zero-cross to avoid visible flicker - is not
+/// generated here. It can't be: this is an ordinary embassy task sharing the
+/// cooperative executor with everything else, including multi-millisecond
+/// flash erases in `persist::persist_task_run`, and no combination of
+/// interrupt priority config changes when a task is *woken*, not when its
+/// `await` points actually get polled. Instead the pulse is wired up
+/// entirely in hardware at task start: a GPIOTE input channel on the
+/// zero-cross pin clears and restarts a hardware timer via PPI, and two more
+/// GPIOTE output channels - one per edge, wired off a pair of that timer's
+/// compare events - latch and then release the gate. None of that depends
+/// on the CPU running at all, let alone this task being scheduled.
+///
+/// The zero-cross input itself still needs the ~1ms debounce the original
+/// software implementation had, to reject opto-isolator glitches. That
+/// debounce window is several times looser than the gate-pulse
+/// budget above, so unlike the pulse it's fine for this task to arbitrate it
+/// purely in software: on every edge it disables `restart_ppi` for
+/// `ZERO_CROSS_DEBOUNCE`, so a second glitch edge arriving inside that
+/// window doesn't retrigger the timer (and drop that half-cycle's pulse).
+///
+/// This task's other remaining job is [`apply_gate_timing`]: recomputing the
+/// two compare values (and enabling/disabling the pulse PPI channels) when
+/// `on` or `brightness` changes. That only has to land sometime before the
+/// next zero-cross - on the order of 10ms of slack at 50Hz mains, nothing
+/// like the original microsecond budget.
 #[embassy_executor::task]
-async fn triac_controller_run(res: TriacResources, signal: &'static TriacSignal) {
-    // note logic level inversion here - we start with high level
-    let mut triac = gpio::Output::new(res.pin, gpio::Level::High, gpio::OutputDrive::Standard);
+async fn triac_controller_run(
+    mut res: TriacResources,
+    signal: &'static TriacSignal,
+    gatt: &'static GattServer,
+    connection_signal: &'static ConnectionSignal,
+    state_changes: &'static StateSignal,
+    countdown: &'static CountdownSignal,
+    initial_on: bool,
+) {
+    let zero_cross = InputChannel::new(
+        res.zero_cross_gpiote,
+        Input::new(res.zero_cross, gpio::Pull::None),
+        InputChannelPolarity::LoToHi,
+    );
+
+    // note logic level inversion here - we start released; the initial state
+    // is only actually applied once the pulse chain below is armed for it.
+    //
+    // Both channels below drive the same physical gate pin with disjoint,
+    // absolute (not toggle) tasks - one only ever clears it, the other only
+    // ever sets it - so unlike a single shared toggle channel, disabling one
+    // mid-pulse can never leave the pin stuck or desync the latch/release
+    // parity. They never run concurrently (cc(0) always fires before
+    // cc(1)), so the two `Output` handles aliasing one pin are never driven
+    // at the same instant.
+    let gate_latch = OutputChannel::new(
+        res.gate_latch_gpiote,
+        gpio::Output::new(unsafe { res.pin.clone_unchecked() }, gpio::Level::High, gpio::OutputDrive::Standard),
+        OutputChannelPolarity::Clear,
+    );
+    let gate_release = OutputChannel::new(
+        res.gate_release_gpiote,
+        gpio::Output::new(res.pin, gpio::Level::High, gpio::OutputDrive::Standard),
+        OutputChannelPolarity::Set,
+    );
+
+    let mut timer = HwTimer::new(res.timer);
+    timer.set_frequency(Frequency::F1MHz);
+
+    // Every zero-cross edge clears and restarts the timer, with no CPU
+    // involvement at all (debounced in software below via `restart_ppi`'s
+    // enabled state).
+    let mut restart_ppi = Ppi::new_one_to_two(
+        res.restart_ppi,
+        zero_cross.event_in(),
+        timer.task_clear(),
+        timer.task_start(),
+    );
+    restart_ppi.enable();
+
+    // These latch the triac (gate low) after the phase delay in cc(0), then
+    // release it (gate high) once the pulse width in cc(1) has elapsed -
+    // again, entirely in hardware. Disabled whenever the lamp is off or at
+    // brightness 0, so the gate is simply never touched.
+    let mut low_ppi = Ppi::new_one_to_one(res.low_ppi, timer.cc(0).event_compare(), gate_latch.task_out());
+    let mut high_ppi = Ppi::new_one_to_one(res.high_ppi, timer.cc(1).event_compare(), gate_release.task_out());
+
+    // Recomputes cc(0)/cc(1) from the current on/brightness state and
+    // enables or disables the pulse PPI channels accordingly. Never called
+    // from the pulse path itself, so ordinary task-scheduling latency here
+    // is fine.
+    let mut apply_gate_timing = |on: bool, brightness: u8| {
+        if !on || brightness == 0 {
+            low_ppi.disable();
+            high_ppi.disable();
+            // If this landed mid-pulse, the release edge will never come -
+            // release the gate by hand so it isn't left latched low.
+            gate_release.set();
+            return;
+        }
+
+        // Clamp so the pulse always completes by the next zero-cross; this
+        // only bites at brightness 1, whose 9.9ms delay would overrun it.
+        let delay_us = MAINS_HALF_PERIOD.as_micros() * (100 - brightness as u64) / 100;
+        let delay_us = delay_us.min((MAINS_HALF_PERIOD - GATE_PULSE).as_micros());
+
+        timer.cc(0).write(delay_us as u32);
+        timer.cc(1).write((delay_us + GATE_PULSE.as_micros()) as u32);
+
+        low_ppi.enable();
+        high_ppi.enable();
+    };
+
+    let mut on = initial_on;
+    let mut brightness: u8 = DEFAULT_BRIGHTNESS;
+    let mut connection: Option<ble::Connection> = None;
+    let mut debouncing = false;
+
+    LAMP_STATE.store(on, Ordering::Relaxed);
+    apply_gate_timing(on, brightness);
 
     loop {
-        let action = signal.wait().await;
-        info!("received triac action '{}'", action);
-        match action {
-            TriacAction::On => triac.set_low(),
-            TriacAction::Off => triac.set_high(),
-            TriacAction::Toggle => triac.toggle(),
+        let debounce_elapsed = async {
+            if debouncing {
+                Timer::after(ZERO_CROSS_DEBOUNCE).await
+            } else {
+                core::future::pending().await
+            }
+        };
+
+        match select4(zero_cross.wait(), signal.wait(), connection_signal.wait(), debounce_elapsed).await {
+            Either4::First(_) => {
+                if !debouncing {
+                    debouncing = true;
+                    restart_ppi.disable();
+                }
+            }
+            Either4::Second(action) => {
+                info!("received triac action '{}'", action);
+                let was_on = on;
+                match action {
+                    TriacAction::On => on = true,
+                    TriacAction::Off => on = false,
+                    TriacAction::Toggle => on = !on,
+                    TriacAction::Brightness(level) => {
+                        brightness = level.min(100);
+                        BRIGHTNESS.store(brightness, Ordering::Relaxed);
+                    }
+                }
+
+                apply_gate_timing(on, brightness);
+
+                if on != was_on {
+                    LAMP_STATE.store(on, Ordering::Relaxed);
+                    state_changes.signal(on);
+
+                    // Any on/off change - whether from BLE, the button, or
+                    // the countdown itself firing - cancels a pending timer.
+                    countdown.signal(0);
+
+                    if let Some(connection) = &connection {
+                        // Best-effort: the peer may have disconnected in the
+                        // meantime, which isn't an error worth tearing down for.
+                        let _ = gatt.control.triac_control_notify(connection, &on);
+                    }
+                }
+            }
+            Either4::Third(new_connection) => connection = Some(new_connection),
+            Either4::Fourth(()) => {
+                debouncing = false;
+                restart_ppi.enable();
+            }
+        }
+    }
+}
+
+/// Task that arms a one-shot auto-off timer on `countdown_secs` writes.
+/// A write of 0, or any new write arriving while already counting down,
+/// replaces whatever's pending rather than stacking timers.
+#[embassy_executor::task]
+async fn countdown_timer_run(countdown: &'static CountdownSignal, triac: &'static TriacSignal) {
+    loop {
+        let mut secs = countdown.wait().await;
+        COUNTDOWN_SECS.store(secs, Ordering::Relaxed);
+
+        while secs != 0 {
+            match select(countdown.wait(), Timer::after(Duration::from_secs(secs.into()))).await {
+                Either::First(new_secs) => {
+                    secs = new_secs;
+                    COUNTDOWN_SECS.store(secs, Ordering::Relaxed);
+                }
+                Either::Second(_) => {
+                    triac.signal(TriacAction::Off);
+                    COUNTDOWN_SECS.store(0, Ordering::Relaxed);
+                    break;
+                }
+            }
         }
     }
 }
@@ -192,13 +521,72 @@ fn init_softdevice() -> &'static mut Softdevice {
 // Main task
 #[embassy_executor::main]
 async fn main(spawner: embassy_executor::Spawner) {
-    let p = init_embassy();
+    let mut p = init_embassy();
+
+    // Holding the button down through boot forces the regular open
+    // advertising even if a bond already exists, e.g. to recover after
+    // losing the paired phone.
+    let force_open_advertising = Input::new(&mut p.P0_19, gpio::Pull::Up).is_low();
+
     let r = split_resources!(p);
 
     let softdevice = init_softdevice();
     static TRIAC_SIGNAL: TriacSignal = TriacSignal::new();
-
-    unwrap!(spawner.spawn(bluetooth_task_run(softdevice, &TRIAC_SIGNAL)));
-    unwrap!(spawner.spawn(triac_controller_run(r.triac, &TRIAC_SIGNAL)));
+    static CONNECTION_SIGNAL: ConnectionSignal = ConnectionSignal::new();
+    static STATE_SIGNAL: StateSignal = StateSignal::new();
+    static BOND_SIGNAL: security::BondSignal = security::BondSignal::new();
+    static COUNTDOWN_SIGNAL: CountdownSignal = CountdownSignal::new();
+
+    static GATT: StaticCell<GattServer> = StaticCell::new();
+    let gatt = GATT.init(unwrap!(GattServer::new(softdevice)));
+
+    // Identify the switch to generic BLE tools via the standard Device
+    // Information Service, and store the git revision to help debug issues
+    // in the field.
+    unwrap!(gatt.info.manufacturer_name_set(&unwrap!("dossalab".try_into())));
+    unwrap!(gatt.info.model_number_set(&unwrap!("nordic-switch".try_into())));
+    unwrap!(gatt.info.hardware_revision_set(&unwrap!("rev-a".try_into())));
+    unwrap!(gatt
+        .info
+        .software_revision_set(&unwrap!(git_version!().try_into())));
+
+    let mut flash = nrf_softdevice::Flash::take(softdevice);
+    let (initial_on, persist_cursor) = persist::read_last_state(&mut flash).await;
+    let initial_bond = persist::read_bond(&mut flash).await;
+
+    // The characteristics otherwise default to `false`/`0`, which would lie
+    // to a client reading them before the first write or zero-cross.
+    unwrap!(gatt.control.triac_control_set(&initial_on));
+    unwrap!(gatt.control.brightness_set(&DEFAULT_BRIGHTNESS));
+
+    static BONDER: StaticCell<Bonder> = StaticCell::new();
+    let bonder: &'static Bonder = BONDER.init(Bonder::new(initial_bond, &BOND_SIGNAL));
+    unwrap!(ble::set_security_handler(bonder));
+
+    unwrap!(spawner.spawn(bluetooth_task_run(
+        softdevice,
+        gatt,
+        &TRIAC_SIGNAL,
+        &CONNECTION_SIGNAL,
+        bonder,
+        force_open_advertising,
+        &COUNTDOWN_SIGNAL,
+    )));
+    unwrap!(spawner.spawn(triac_controller_run(
+        r.triac,
+        &TRIAC_SIGNAL,
+        gatt,
+        &CONNECTION_SIGNAL,
+        &STATE_SIGNAL,
+        &COUNTDOWN_SIGNAL,
+        initial_on,
+    )));
     unwrap!(spawner.spawn(button_listener_run(r.button, &TRIAC_SIGNAL)));
+    unwrap!(spawner.spawn(countdown_timer_run(&COUNTDOWN_SIGNAL, &TRIAC_SIGNAL)));
+    unwrap!(spawner.spawn(persist::persist_task_run(
+        flash,
+        persist_cursor,
+        &STATE_SIGNAL,
+        &BOND_SIGNAL
+    )));
 }
@@ -0,0 +1,284 @@
+//! Flash persistence: the last commanded lamp state, wear-leveled, and the
+//! single bond this switch remembers.
+//!
+//! Two raw flash pages are used as an append-only log for the lamp state:
+//! committing a state change appends a small record instead of rewriting a
+//! whole page, and a page is only erased once it fills up - by then
+//! thousands of toggles have gone by. On boot both pages are replayed and
+//! the record with the highest sequence number wins, which tolerates a
+//! half-written final record by simply stopping the scan at the first
+//! invalid slot. The bond record lives on its own page and, since bonding is
+//! rare, is just overwritten in place rather than wear-leveled.
+
+use core::mem::size_of;
+
+use defmt::warn;
+use embassy_futures::select::{select3, Either3};
+use embassy_time::{Duration, Timer};
+use embedded_storage_async::nor_flash::NorFlash;
+use nrf_softdevice::Flash;
+
+use crate::security::{Bond, BondSignal};
+use crate::StateSignal;
+
+/// Physical pages backing the lamp-state log. Must match the `storage`
+/// region carved out of the linker script, outside of the SoftDevice and
+/// application flash.
+const PAGE_ADDRESSES: [u32; 2] = [0xF8000, 0xF9000];
+const PAGE_SIZE: u32 = 4096;
+
+/// marker + sequence (u16) + state + checksum, padded to the flash write
+/// granularity. `seq` has to be wide enough that its wraparound comparison
+/// in `is_newer` stays valid across a full page - at `u8` a page's ~500
+/// records could wrap it twice before the other page is touched again,
+/// picking the wrong page as newest.
+const RECORD_SIZE: u32 = 8;
+const RECORD_MAGIC: u8 = 0xA5;
+
+/// How long the state must stay unchanged before it's committed to flash, so
+/// rapid toggling (a flaky button, a chatty client) doesn't wear the page out.
+const WRITE_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Single page holding the one bond this switch remembers. Bonding is a rare,
+/// user-initiated action, so unlike the lamp-state log this is just
+/// overwritten in place (erase + write) rather than wear-leveled.
+const BOND_PAGE: u32 = 0xF7000;
+const BOND_RECORD_SIZE: usize = 1 + size_of::<Bond>() + 4;
+
+/// CRC-32/ISO-HDLC, computed byte-at-a-time (the bond record is written at
+/// most once per pairing, so a lookup table buys nothing). Guards the raw
+/// byte reinterpretation in [`read_bond`]: a 1-byte XOR fold has a 1-in-256
+/// chance of passing on exactly the kind of corruption a torn write tends to
+/// produce, which isn't good enough odds to feed into a
+/// `MaybeUninit::assume_init()` of SoftDevice FFI key material.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn checksum(magic: u8, seq: u16, state: u8) -> u8 {
+    let [seq_lo, seq_hi] = seq.to_le_bytes();
+    magic ^ seq_lo ^ seq_hi ^ state
+}
+
+fn encode(seq: u16, state: bool) -> [u8; RECORD_SIZE as usize] {
+    let state = state as u8;
+    let [seq_lo, seq_hi] = seq.to_le_bytes();
+    // Trailing padding is left at the erased value (0xFF).
+    [RECORD_MAGIC, seq_lo, seq_hi, state, checksum(RECORD_MAGIC, seq, state), 0xFF, 0xFF, 0xFF]
+}
+
+fn decode(record: &[u8; RECORD_SIZE as usize]) -> Option<(u16, bool)> {
+    let [magic, seq_lo, seq_hi, state, crc, ..] = *record;
+    let seq = u16::from_le_bytes([seq_lo, seq_hi]);
+    if magic != RECORD_MAGIC || crc != checksum(magic, seq, state) {
+        return None;
+    }
+    Some((seq, state != 0))
+}
+
+/// True if `a`'s sequence number is strictly newer than `b`'s, tolerating wraparound.
+fn is_newer(a: u16, b: u16) -> bool {
+    let diff = a.wrapping_sub(b);
+    diff != 0 && (diff as i16) > 0
+}
+
+/// Result of replaying a single page: the last valid record found (if any)
+/// and the offset right after it, where appending should resume.
+struct PageState {
+    last: Option<(u16, bool)>,
+    next_offset: u32,
+}
+
+async fn scan_page(flash: &mut Flash, page: u32) -> PageState {
+    let mut last = None;
+    let mut offset = 0;
+
+    while offset + RECORD_SIZE <= PAGE_SIZE {
+        let mut buf = [0u8; RECORD_SIZE as usize];
+        if flash.read(page + offset, &mut buf).await.is_err() {
+            break;
+        }
+
+        match decode(&buf) {
+            Some(record) => {
+                last = Some(record);
+                offset += RECORD_SIZE;
+            }
+            // Erased (0xFF) or torn record - the rest of the page is unwritten.
+            None => break,
+        }
+    }
+
+    PageState {
+        last,
+        next_offset: offset,
+    }
+}
+
+/// A location to resume appending at: which page, the next free offset in
+/// it, and the next sequence number to use.
+pub(crate) struct Cursor {
+    page: u32,
+    offset: u32,
+    seq: u16,
+}
+
+/// Replays both pages and returns the last commanded state together with
+/// where to resume writing. Call once at boot, before spawning the task that
+/// owns `flash` from then on.
+pub(crate) async fn read_last_state(flash: &mut Flash) -> (bool, Cursor) {
+    let pages = [
+        scan_page(flash, PAGE_ADDRESSES[0]).await,
+        scan_page(flash, PAGE_ADDRESSES[1]).await,
+    ];
+
+    let newest = match (pages[0].last, pages[1].last) {
+        (Some(a), Some(b)) if is_newer(b.0, a.0) => Some(1),
+        (Some(_), Some(_)) => Some(0),
+        (Some(_), None) => Some(0),
+        (None, Some(_)) => Some(1),
+        (None, None) => None,
+    };
+
+    let Some(page_index) = newest else {
+        return (
+            false,
+            Cursor {
+                page: PAGE_ADDRESSES[0],
+                offset: 0,
+                seq: 0,
+            },
+        );
+    };
+
+    let (seq, state) = pages[page_index].last.unwrap();
+    (
+        state,
+        Cursor {
+            page: PAGE_ADDRESSES[page_index],
+            offset: pages[page_index].next_offset,
+            seq: seq.wrapping_add(1),
+        },
+    )
+}
+
+/// Reads back the bonded peer, if a valid record is present.
+///
+/// # Safety
+/// `Bond` is a plain collection of SoftDevice key-material structs with no
+/// padding-sensitive invariants, so reinterpreting it as bytes (and back) is
+/// sound - but only for bytes that actually came from [`write_bond`] on the
+/// same firmware build, which is what the CRC-32 below is for: a corrupted
+/// or torn record is rejected before it ever reaches `assume_init()`.
+pub(crate) async fn read_bond(flash: &mut Flash) -> Option<Bond> {
+    let mut buf = [0u8; BOND_RECORD_SIZE];
+    if flash.read(BOND_PAGE, &mut buf).await.is_err() {
+        return None;
+    }
+
+    let (magic, rest) = buf.split_first()?;
+    let (payload, crc_bytes) = rest.split_at(rest.len() - 4);
+    let crc = u32::from_le_bytes(crc_bytes.try_into().ok()?);
+    if *magic != RECORD_MAGIC || crc != crc32(payload) {
+        return None;
+    }
+
+    let mut bond = core::mem::MaybeUninit::<Bond>::uninit();
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            payload.as_ptr(),
+            bond.as_mut_ptr() as *mut u8,
+            size_of::<Bond>(),
+        );
+        Some(bond.assume_init())
+    }
+}
+
+async fn write_bond(flash: &mut Flash, bond: Bond) {
+    let mut buf = [0u8; BOND_RECORD_SIZE];
+    buf[0] = RECORD_MAGIC;
+
+    let payload = &mut buf[1..1 + size_of::<Bond>()];
+    unsafe {
+        core::ptr::copy_nonoverlapping(&bond as *const Bond as *const u8, payload.as_mut_ptr(), size_of::<Bond>());
+    }
+    let crc = crc32(payload);
+    buf[1 + size_of::<Bond>()..].copy_from_slice(&crc.to_le_bytes());
+
+    if flash.erase(BOND_PAGE, BOND_PAGE + PAGE_SIZE).await.is_err()
+        || flash.write(BOND_PAGE, &buf).await.is_err()
+    {
+        warn!("failed to persist bond to flash");
+    }
+}
+
+async fn append(flash: &mut Flash, cursor: &mut Cursor, state: bool) {
+    if cursor.offset + RECORD_SIZE > PAGE_SIZE {
+        let other = if cursor.page == PAGE_ADDRESSES[0] {
+            PAGE_ADDRESSES[1]
+        } else {
+            PAGE_ADDRESSES[0]
+        };
+
+        if flash.erase(other, other + PAGE_SIZE).await.is_err() {
+            warn!("failed to erase flash page at {=u32:x}, state not persisted", other);
+            return;
+        }
+
+        cursor.page = other;
+        cursor.offset = 0;
+    }
+
+    let record = encode(cursor.seq, state);
+    if flash.write(cursor.page + cursor.offset, &record).await.is_err() {
+        warn!("failed to persist lamp state to flash");
+        return;
+    }
+
+    cursor.offset += RECORD_SIZE;
+    cursor.seq = cursor.seq.wrapping_add(1);
+}
+
+/// Task that owns the flash peripheral. It debounces lamp-state writes so a
+/// burst of toggles costs at most one flash write once stable for
+/// `WRITE_DEBOUNCE`, and commits bond changes immediately since those only
+/// happen once per pairing.
+#[embassy_executor::task]
+pub(crate) async fn persist_task_run(
+    mut flash: Flash,
+    mut cursor: Cursor,
+    state_changes: &'static StateSignal,
+    bond_changes: &'static BondSignal,
+) {
+    let mut pending_state: Option<bool> = None;
+
+    loop {
+        let debounce = async {
+            match pending_state {
+                Some(_) => Timer::after(WRITE_DEBOUNCE).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        match select3(state_changes.wait(), bond_changes.wait(), debounce).await {
+            Either3::First(state) => pending_state = Some(state),
+            Either3::Second(bond) => write_bond(&mut flash, bond).await,
+            Either3::Third(()) => {
+                if let Some(state) = pending_state.take() {
+                    append(&mut flash, &mut cursor, state).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Bonding/pairing security.
+//!
+//! Encryption and bonding are required before a connection's writes to
+//! `ControlService` are honored - see [`is_secure`]. [`Bonder`] implements
+//! the SoftDevice's bond-storage callbacks; since those are synchronous, the
+//! actual flash write is handed off to [`crate::persist`] via [`BondSignal`].
+
+use core::cell::Cell;
+
+use defmt::warn;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use heapless::Vec;
+use nrf_softdevice::ble::security::{IoCapabilities, SecurityHandler};
+use nrf_softdevice::ble::{Connection, EncryptionInfo, IdentityKey, MasterId, SecurityMode};
+
+/// Maximum size of the GATT system attributes blob we're willing to cache
+/// for a bonded peer (CCCD state etc).
+const MAX_SYS_ATTRS: usize = 62;
+
+/// Everything nrf-softdevice needs to resume an encrypted link with a
+/// previously bonded peer without pairing again.
+#[derive(Clone, Copy)]
+pub(crate) struct Bond {
+    pub(crate) master_id: MasterId,
+    pub(crate) key: EncryptionInfo,
+    pub(crate) peer_id: IdentityKey,
+}
+
+/// Channel the `Bonder` uses to hand new bond data to the flash persistence
+/// task running in `persist`.
+pub(crate) type BondSignal = Signal<CriticalSectionRawMutex, Bond>;
+
+/// True if `connection` is both encrypted and belongs to the peer `bonder`
+/// actually has a saved bond for, i.e. control writes over it can be
+/// trusted. Encryption alone isn't enough: a central can encrypt a link with
+/// temporary (non-bonded) keys, so the peer's address is cross-checked
+/// against the stored bond as well.
+pub(crate) fn is_secure(connection: &Connection, bonder: &Bonder) -> bool {
+    let encrypted = matches!(
+        connection.security_mode(),
+        SecurityMode::JustWorks | SecurityMode::Mitm | SecurityMode::LescMitm
+    );
+
+    let bonded = bonder
+        .peer()
+        .is_some_and(|peer| peer.addr == connection.peer_address());
+
+    encrypted && bonded
+}
+
+/// Single-slot bond store: this switch is a single-user device and only
+/// remembers the most recently paired phone.
+pub(crate) struct Bonder {
+    bond: Cell<Option<Bond>>,
+    sys_attrs: Cell<Vec<u8, MAX_SYS_ATTRS>>,
+    bond_changes: &'static BondSignal,
+}
+
+impl Bonder {
+    pub(crate) fn new(initial: Option<Bond>, bond_changes: &'static BondSignal) -> Self {
+        Self {
+            bond: Cell::new(initial),
+            sys_attrs: Cell::new(Vec::new()),
+            bond_changes,
+        }
+    }
+
+    /// The bonded peer's identity, if any - used to pick directed
+    /// advertising in `main::advertise`.
+    pub(crate) fn peer(&self) -> Option<IdentityKey> {
+        self.bond.get().map(|bond| bond.peer_id)
+    }
+}
+
+impl SecurityHandler for Bonder {
+    fn io_capabilities(&self) -> IoCapabilities {
+        IoCapabilities::None
+    }
+
+    fn can_bond(&self, _conn: &Connection) -> bool {
+        true
+    }
+
+    fn save_bond(
+        &self,
+        _conn: &Connection,
+        master_id: MasterId,
+        key: EncryptionInfo,
+        peer_id: IdentityKey,
+    ) {
+        let bond = Bond {
+            master_id,
+            key,
+            peer_id,
+        };
+
+        self.bond.set(Some(bond));
+        self.bond_changes.signal(bond);
+    }
+
+    fn load_bond(&self, master_id: MasterId) -> Option<(EncryptionInfo, IdentityKey)> {
+        let bond = self.bond.get()?;
+        (bond.master_id == master_id).then_some((bond.key, bond.peer_id))
+    }
+
+    fn save_sys_attrs(&self, _conn: &Connection, sys_attrs: &[u8]) {
+        let mut buf = Vec::new();
+        if buf.extend_from_slice(sys_attrs).is_err() {
+            warn!("system attributes too large to cache, dropping");
+        }
+        self.sys_attrs.set(buf);
+    }
+
+    fn load_sys_attrs(&self) -> Option<Vec<u8, MAX_SYS_ATTRS>> {
+        let attrs = self.sys_attrs.take();
+        self.sys_attrs.set(attrs.clone());
+        (!attrs.is_empty()).then_some(attrs)
+    }
+}